@@ -7,14 +7,75 @@
  * Copyright 2023 Saso Kiselkov. All rights reserved.
  */
 
+use std::env;
+use std::path::PathBuf;
+
+/* Where libelec.h lives, relative to this crate. */
+const LIBELEC_INCLUDE_DIR: &str = "../src";
+
 fn main() {
-	const LACF_DIR: &str = "../nettest/libacfutils-redist-v0.37";
-	println!("cargo:rustc-link-search=native=../src/build");
-	println!("cargo:rustc-link-lib=static=elec");
-	println!("cargo:rustc-link-search=native={}/lin64/lib", LACF_DIR);
-	println!("cargo:rustc-link-lib=static=acfutils");
-	println!("cargo:rustc-link-lib=static=crypto");
-	println!("cargo:rustc-link-lib=static=ssl");
-	println!("cargo:rustc-link-lib=static=curl");
-	println!("cargo:rustc-link-lib=static=z");
+	/*
+	 * Under the "dlopen" feature libelec (and its own static deps) is
+	 * never linked in at build time. Instead, ElecSys::new() locates and
+	 * loads it at runtime via `libloading` and resolves every
+	 * `libelec_*` symbol as the library is loaded (see src/elec.rs).
+	 * There is nothing for the linker to do in that configuration.
+	 */
+	if !cfg!(feature = "dlopen") {
+		const LACF_DIR: &str = "../nettest/libacfutils-redist-v0.37";
+		println!("cargo:rustc-link-search=native=../src/build");
+		println!("cargo:rustc-link-lib=static=elec");
+		println!("cargo:rustc-link-search=native={}/lin64/lib", LACF_DIR);
+		println!("cargo:rustc-link-lib=static=acfutils");
+		println!("cargo:rustc-link-lib=static=crypto");
+		println!("cargo:rustc-link-lib=static=ssl");
+		println!("cargo:rustc-link-lib=static=curl");
+		println!("cargo:rustc-link-lib=static=z");
+	}
+
+	gen_bindings();
+}
+
+/*
+ * Regenerates the `sys` module from libelec.h instead of hand-maintaining
+ * the extern block. This is the only thing that needs to know the C
+ * signatures; everything in src/elec.rs builds safe wrappers on top of
+ * whatever this emits, so a reordered enum or a changed prototype turns
+ * into a compile error here rather than a silent ABI mismatch at runtime.
+ */
+fn gen_bindings() {
+	println!("cargo:rerun-if-changed={}/libelec.h", LIBELEC_INCLUDE_DIR);
+
+	let mut builder = bindgen::Builder::default()
+	    .header(format!("{}/libelec.h", LIBELEC_INCLUDE_DIR))
+	    .allowlist_type("elec_t")
+	    .allowlist_type("elec_comp_t")
+	    .allowlist_type("elec_comp_type")
+	    .allowlist_var("ELEC_MAX_SRCS")
+	    .allowlist_function("libelec_.*")
+	    .opaque_type("elec_t")
+	    .opaque_type("elec_comp_t")
+	    .default_enum_style(bindgen::EnumVariation::Rust {
+		    non_exhaustive: false,
+	    })
+	    .derive_debug(true)
+	    .derive_eq(true);
+
+	/*
+	 * bindgen's own dynamic-loading mode emits a `libelec` struct that
+	 * loads every allowlisted function through `libloading` on
+	 * construction, which is exactly the function-pointer table the
+	 * "dlopen" feature needs -- no more hand-rolled symbol table.
+	 */
+	if cfg!(feature = "dlopen") {
+		builder = builder
+		    .dynamic_library_name("libelec")
+		    .dynamic_link_require_all(true);
+	}
+
+	let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+	builder.generate()
+	    .expect("unable to generate libelec bindings")
+	    .write_to_file(out_dir.join("bindings.rs"))
+	    .expect("unable to write libelec bindings");
 }
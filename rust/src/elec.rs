@@ -14,16 +14,22 @@ use std::os::raw::c_char;
 use std::os::raw::c_void;
 use std::ffi::CString;
 use std::ffi::CStr;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
 
 pub struct ElecSys {
-	elec: *mut elec_t
+	elec: *mut sys::elec_t
 }
 
 impl ElecSys {
+	#[cfg(not(feature = "dlopen"))]
 	pub fn new(filename: &str) -> Option<ElecSys> {
+		let syms = SYMS.get_or_init(|| Syms);
 		let elec = unsafe {
 			let c_filename = CString::new(filename).unwrap();
-			libelec_new(c_filename.as_ptr())
+			syms.libelec_new(c_filename.as_ptr())
 		};
 		if !elec.is_null() {
 			Some(ElecSys{elec: elec})
@@ -31,52 +37,112 @@ impl ElecSys {
 			None
 		}
 	}
+	/*
+	 * Under the "dlopen" feature, libelec isn't linked at build time, so
+	 * the first call to `new()` that manages to load the library picks
+	 * the library that every subsequent ElecSys/ElecComp in the process
+	 * will use, even if that call's libelec_new() then fails; `lib_path`
+	 * is ignored on every later call. `lib_path` may name a path or bare
+	 * library name to hand to the platform loader; pass None to use the
+	 * default "libelec.so", "libelec.dylib" or "elec.dll".
+	 */
+	#[cfg(feature = "dlopen")]
+	pub fn new(filename: &str, lib_path: Option<&str>) ->
+	    Result<ElecSys, LibElecError> {
+		let syms = match SYMS.get() {
+			Some(syms) => syms,
+			None => {
+				let path = lib_path.unwrap_or(DEFAULT_LIB_NAME);
+				let lib = unsafe { sys::libelec::new(path) }
+				    .map_err(LibElecError::Load)?;
+				SYMS.get_or_init(|| Syms(lib))
+			}
+		};
+		let elec = unsafe {
+			let c_filename = CString::new(filename).unwrap();
+			syms.libelec_new(c_filename.as_ptr())
+		};
+		if !elec.is_null() {
+			Ok(ElecSys{elec: elec})
+		} else {
+			Err(LibElecError::Init)
+		}
+	}
 	pub fn start(&mut self) -> bool {
 		unsafe {
-			libelec_sys_start(self.elec)
+			syms().libelec_sys_start(self.elec)
 		}
 	}
 	pub fn stop(&mut self) {
 		unsafe {
-			libelec_sys_stop(self.elec)
+			syms().libelec_sys_stop(self.elec)
 		}
 	}
 	pub fn is_started(&self) -> bool {
 		unsafe {
-			libelec_sys_is_started(self.elec)
+			syms().libelec_sys_is_started(self.elec)
 		}
 	}
 	pub fn can_start(&self) -> bool {
 		unsafe {
-			libelec_sys_can_start(self.elec)
+			syms().libelec_sys_can_start(self.elec)
 		}
 	}
 	pub fn sys_set_time_factor(&mut self, time_factor: f64) {
 		unsafe {
-			libelec_sys_set_time_factor(self.elec, time_factor)
+			syms().libelec_sys_set_time_factor(self.elec, time_factor)
 		}
 	}
 	pub fn sys_get_time_factor(&self) -> f64 {
 		unsafe {
-			libelec_sys_get_time_factor(self.elec)
+			syms().libelec_sys_get_time_factor(self.elec)
 		}
 	}
-	pub fn add_user_cb(&mut self, pre: bool, cb: elec_user_cb_t,
+	pub fn add_user_cb(&mut self, pre: bool, cb: sys::elec_user_cb_t,
 	    userinfo: *mut c_void) {
 		unsafe {
-			libelec_add_user_cb(self.elec, pre, cb, userinfo)
+			syms().libelec_add_user_cb(self.elec, pre, cb, userinfo)
 		}
 	}
-	pub fn remove_user_cb(&mut self, pre: bool, cb: elec_user_cb_t,
+	pub fn remove_user_cb(&mut self, pre: bool, cb: sys::elec_user_cb_t,
 	    userinfo: *mut c_void) {
 		unsafe {
-			libelec_remove_user_cb(self.elec, pre, cb, userinfo)
+			syms().libelec_remove_user_cb(self.elec, pre, cb, userinfo)
+		}
+	}
+	/*
+	 * Safe closure-based equivalents of add_user_cb()/remove_user_cb().
+	 * The closure is boxed and parked in STEP_CBS, keyed by this system's
+	 * `elec` pointer, so it lives exactly as long as the ElecSys does;
+	 * Drop below clears out its entries before the system goes away.
+	 * There's no remove_pre_step()/remove_post_step(): once added, a step
+	 * closure lives for the ElecSys's lifetime.
+	 */
+	pub fn add_pre_step(&mut self,
+	    f: impl FnMut(&mut ElecSys) + Send + 'static) {
+		self.add_step_cb(true, f);
+	}
+	pub fn add_post_step(&mut self,
+	    f: impl FnMut(&mut ElecSys) + Send + 'static) {
+		self.add_step_cb(false, f);
+	}
+	fn add_step_cb(&mut self, pre: bool,
+	    f: impl FnMut(&mut ElecSys) + Send + 'static) {
+		let mut ctx = Box::new(StepCtx{elec: self.elec, cb: Box::new(f)});
+		let userinfo = ctx.as_mut() as *mut StepCtx as *mut c_void;
+		step_cbs().lock().unwrap()
+		    .entry(self.elec as usize)
+		    .or_insert_with(Vec::new)
+		    .push(ctx);
+		unsafe {
+			syms().libelec_add_user_cb(self.elec, pre,
+			    Some(step_cb_trampoline), userinfo)
 		}
 	}
 	pub fn comp_find(&self, name: &str) -> Option<ElecComp> {
 		let comp = unsafe {
 			let c_name = CString::new(name).unwrap();
-			libelec_comp_find(self.elec, c_name.as_ptr())
+			syms().libelec_comp_find(self.elec, c_name.as_ptr())
 		};
 		if !comp.is_null() {
 			Some(ElecComp{comp: comp})
@@ -84,7 +150,7 @@ impl ElecSys {
 			None
 		}
 	}
-	extern "C" fn comp_walk_cb(comp: *mut elec_comp_t,
+	extern "C" fn comp_walk_cb(comp: *mut sys::elec_comp_t,
 	    userinfo: *mut c_void) {
 		unsafe {
 			let comps = userinfo as *mut Vec<ElecComp>;
@@ -95,7 +161,7 @@ impl ElecSys {
 		let mut comps: Vec<ElecComp> = vec![];
 		unsafe {
 			let comps_ptr: *mut Vec<ElecComp> = &mut comps;
-			libelec_walk_comps(self.elec, Self::comp_walk_cb,
+			syms().libelec_walk_comps(self.elec, Some(Self::comp_walk_cb),
 			    comps_ptr as *mut c_void)
 		};
 		comps
@@ -105,33 +171,89 @@ impl ElecSys {
 impl Drop for ElecSys {
 	fn drop(&mut self) {
 		unsafe {
-			if libelec_sys_is_started(self.elec) {
-				libelec_sys_stop(self.elec);
+			if syms().libelec_sys_is_started(self.elec) {
+				syms().libelec_sys_stop(self.elec);
+			}
+		}
+		/*
+		 * Drop the Rust-side closures before tearing down the C side:
+		 * once libelec_destroy() runs, the component pointers below go
+		 * away, so this is the last point at which `all_comps()` is
+		 * still valid.
+		 */
+		{
+			let mut comp_cbs = comp_cbs().lock().unwrap();
+			for comp in self.all_comps() {
+				comp_cbs.remove(&(comp.comp as usize));
 			}
-			libelec_destroy(self.elec);
+		}
+		step_cbs().lock().unwrap().remove(&(self.elec as usize));
+		unsafe {
+			syms().libelec_destroy(self.elec);
 		}
 	}
 }
 
 #[derive(Clone, Copy)]
 pub struct ElecComp {
-	comp: *mut elec_comp_t
+	comp: *mut sys::elec_comp_t
 }
 
-#[derive(Debug, PartialEq)]
-#[repr(C)]
+/*
+ * Hand-written mirror of the generated `sys::elec_comp_type`, kept around
+ * so callers get ergonomic variant names instead of the raw C ones. The
+ * `const _` block below pins each discriminant to its bindgen-generated
+ * counterpart, so if libelec.h ever reorders `elec_comp_type` this fails
+ * to compile instead of silently asserting on the wrong variant at
+ * runtime.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
 pub enum CompType {
-	Batt,
-	Gen,
-	TRU,
-	Inv,
-	Load,
-	Bus,
-	CB,
-	Shunt,
-	Tie,
-	Diode,
-	LabelBox
+	Batt = 0,
+	Gen = 1,
+	TRU = 2,
+	Inv = 3,
+	Load = 4,
+	Bus = 5,
+	CB = 6,
+	Shunt = 7,
+	Tie = 8,
+	Diode = 9,
+	LabelBox = 10,
+}
+
+const _: () = {
+	assert!(CompType::Batt as i32 == sys::elec_comp_type::ELEC_BATT as i32);
+	assert!(CompType::Gen as i32 == sys::elec_comp_type::ELEC_GEN as i32);
+	assert!(CompType::TRU as i32 == sys::elec_comp_type::ELEC_TRU as i32);
+	assert!(CompType::Inv as i32 == sys::elec_comp_type::ELEC_INV as i32);
+	assert!(CompType::Load as i32 == sys::elec_comp_type::ELEC_LOAD as i32);
+	assert!(CompType::Bus as i32 == sys::elec_comp_type::ELEC_BUS as i32);
+	assert!(CompType::CB as i32 == sys::elec_comp_type::ELEC_CB as i32);
+	assert!(CompType::Shunt as i32 == sys::elec_comp_type::ELEC_SHUNT as i32);
+	assert!(CompType::Tie as i32 == sys::elec_comp_type::ELEC_TIE as i32);
+	assert!(CompType::Diode as i32 == sys::elec_comp_type::ELEC_DIODE as i32);
+	assert!(CompType::LabelBox as i32 ==
+	    sys::elec_comp_type::ELEC_LABEL_BOX as i32);
+};
+
+impl From<sys::elec_comp_type> for CompType {
+	fn from(t: sys::elec_comp_type) -> Self {
+		match t {
+			sys::elec_comp_type::ELEC_BATT => CompType::Batt,
+			sys::elec_comp_type::ELEC_GEN => CompType::Gen,
+			sys::elec_comp_type::ELEC_TRU => CompType::TRU,
+			sys::elec_comp_type::ELEC_INV => CompType::Inv,
+			sys::elec_comp_type::ELEC_LOAD => CompType::Load,
+			sys::elec_comp_type::ELEC_BUS => CompType::Bus,
+			sys::elec_comp_type::ELEC_CB => CompType::CB,
+			sys::elec_comp_type::ELEC_SHUNT => CompType::Shunt,
+			sys::elec_comp_type::ELEC_TIE => CompType::Tie,
+			sys::elec_comp_type::ELEC_DIODE => CompType::Diode,
+			sys::elec_comp_type::ELEC_LABEL_BOX => CompType::LabelBox,
+		}
+	}
 }
 
 impl ElecComp {
@@ -140,7 +262,7 @@ impl ElecComp {
 	 */
 	pub fn get_name(&self) -> String {
 		unsafe {
-			CStr::from_ptr(libelec_comp_get_name(self.comp))
+			CStr::from_ptr(syms().libelec_comp_get_name(self.comp))
 			    .to_str()
 			    .unwrap()
 			    .to_string()
@@ -148,74 +270,74 @@ impl ElecComp {
 	}
 	fn get_type(&self) -> CompType {
 		unsafe {
-			libelec_comp_get_type(self.comp)
+			syms().libelec_comp_get_type(self.comp).into()
 		}
 	}
 	pub fn get_location(&self) -> String {
 		unsafe {
-			CStr::from_ptr(libelec_comp_get_location(self.comp))
+			CStr::from_ptr(syms().libelec_comp_get_location(self.comp))
 			    .to_str()
 			    .unwrap()
 			    .to_string()
 		}
 	}
 	pub fn get_autogen(&self) -> bool {
-		unsafe { libelec_comp_get_autogen(self.comp) }
+		unsafe { syms().libelec_comp_get_autogen(self.comp) }
 	}
 	pub fn get_num_conns(&self) -> usize {
-		unsafe { libelec_comp_get_num_conns(self.comp) }
+		unsafe { syms().libelec_comp_get_num_conns(self.comp) }
 	}
 	pub fn get_conn(&self, i: usize) -> ElecComp {
 		unsafe {
 			assert!(i < Self::get_num_conns(self));
-			ElecComp{comp: libelec_comp_get_conn(self.comp, i)}
+			ElecComp{comp: syms().libelec_comp_get_conn(self.comp, i)}
 		}
 	}
 	#[allow(non_snake_case)]
 	pub fn is_AC(&self) -> bool {
-		unsafe { libelec_comp_is_AC(self.comp) }
+		unsafe { syms().libelec_comp_is_AC(self.comp) }
 	}
 	/*
 	 * Electrical state interrogation
 	 */
 	pub fn in_volts(&self) -> f64 {
-		unsafe { libelec_comp_get_in_volts(self.comp) }
+		unsafe { syms().libelec_comp_get_in_volts(self.comp) }
 	}
 	pub fn out_volts(&self) -> f64 {
-		unsafe { libelec_comp_get_out_volts(self.comp) }
+		unsafe { syms().libelec_comp_get_out_volts(self.comp) }
 	}
 	pub fn in_amps(&self) -> f64 {
-		unsafe { libelec_comp_get_in_amps(self.comp) }
+		unsafe { syms().libelec_comp_get_in_amps(self.comp) }
 	}
 	pub fn out_amps(&self) -> f64 {
-		unsafe { libelec_comp_get_out_amps(self.comp) }
+		unsafe { syms().libelec_comp_get_out_amps(self.comp) }
 	}
 	pub fn in_pwr(&self) -> f64 {
-		unsafe { libelec_comp_get_in_pwr(self.comp) }
+		unsafe { syms().libelec_comp_get_in_pwr(self.comp) }
 	}
 	pub fn out_pwr(&self) -> f64 {
-		unsafe { libelec_comp_get_out_pwr(self.comp) }
+		unsafe { syms().libelec_comp_get_out_pwr(self.comp) }
 	}
 	pub fn in_freq(&self) -> f64 {
-		unsafe { libelec_comp_get_in_freq(self.comp) }
+		unsafe { syms().libelec_comp_get_in_freq(self.comp) }
 	}
 	pub fn out_freq(&self) -> f64 {
-		unsafe { libelec_comp_get_out_freq(self.comp) }
+		unsafe { syms().libelec_comp_get_out_freq(self.comp) }
 	}
 	pub fn incap_volts(&self) -> f64 {
-		unsafe { libelec_comp_get_incap_volts(self.comp) }
+		unsafe { syms().libelec_comp_get_incap_volts(self.comp) }
 	}
 	pub fn is_powered(&self) -> bool {
-		unsafe { libelec_comp_is_powered(self.comp) }
+		unsafe { syms().libelec_comp_is_powered(self.comp) }
 	}
 	pub fn get_eff(&self) -> f64 {
-		unsafe { libelec_comp_get_eff(self.comp) }
+		unsafe { syms().libelec_comp_get_eff(self.comp) }
 	}
 	pub fn get_srcs(&self) -> Vec<ElecComp> {
-		let mut srcs_array: [*mut elec_comp_t; ELEC_MAX_SRCS] =
-		    [std::ptr::null_mut(); ELEC_MAX_SRCS];
+		let mut srcs_array: [*mut sys::elec_comp_t; sys::ELEC_MAX_SRCS as usize] =
+		    [std::ptr::null_mut(); sys::ELEC_MAX_SRCS as usize];
 		let n = unsafe {
-			libelec_comp_get_srcs(self.comp, &mut srcs_array)
+			syms().libelec_comp_get_srcs(self.comp, srcs_array.as_mut_ptr())
 		};
 		let mut srcs: Vec<ElecComp> = vec![];
 		for i in 0 .. n {
@@ -227,67 +349,67 @@ impl ElecComp {
 	 * Failures
 	 */
 	pub fn set_failed(&mut self, failed: bool) {
-		unsafe { libelec_comp_set_failed(self.comp, failed) }
+		unsafe { syms().libelec_comp_set_failed(self.comp, failed) }
 	}
 	pub fn get_failed(&self) -> bool {
-		unsafe { libelec_comp_get_failed(self.comp) }
+		unsafe { syms().libelec_comp_get_failed(self.comp) }
 	}
 	pub fn set_shorted(&mut self, shorted: bool) {
-		unsafe { libelec_comp_set_shorted(self.comp, shorted) }
+		unsafe { syms().libelec_comp_set_shorted(self.comp, shorted) }
 	}
 	pub fn get_shorted(&self) -> bool {
-		unsafe { libelec_comp_get_shorted(self.comp) }
+		unsafe { syms().libelec_comp_get_shorted(self.comp) }
 	}
 	pub fn set_random_volts(&mut self, stddev: f64) -> f64 {
-		unsafe { libelec_gen_set_random_volts(self.comp, stddev) }
+		unsafe { syms().libelec_gen_set_random_volts(self.comp, stddev) }
 	}
 	pub fn set_random_freq(&mut self, stddev: f64) -> f64 {
-		unsafe { libelec_gen_set_random_freq(self.comp, stddev) }
+		unsafe { syms().libelec_gen_set_random_freq(self.comp, stddev) }
 	}
 	/*
 	 * CBs
 	 */
 	pub fn cb_set(&mut self, set: bool) {
 		assert_eq!(self.get_type(), CompType::CB);
-		unsafe { libelec_cb_set(self.comp, set) }
+		unsafe { syms().libelec_cb_set(self.comp, set) }
 	}
 	pub fn cb_get(&self) -> bool {
 		assert_eq!(self.get_type(), CompType::CB);
-		unsafe { libelec_cb_get(self.comp) }
+		unsafe { syms().libelec_cb_get(self.comp) }
 	}
 	pub fn cb_get_temp(&self) -> f64 {
 		assert_eq!(self.get_type(), CompType::CB);
-		unsafe { libelec_cb_get_temp(self.comp) }
+		unsafe { syms().libelec_cb_get_temp(self.comp) }
 	}
 	/*
 	 * Ties
 	 */
 	pub fn tie_set_list(&mut self, list: &Vec<ElecComp>) {
 		assert_eq!(self.get_type(), CompType::Tie);
-		let comps: Vec<*const elec_comp_t> = list.iter()
-		    .map(|c| c.comp as *const elec_comp_t)
+		let comps: Vec<*const sys::elec_comp_t> = list.iter()
+		    .map(|c| c.comp as *const sys::elec_comp_t)
 		    .collect();
 		unsafe {
-			libelec_tie_set_list(self.comp, comps.len(),
-			    comps.as_ptr() as *const*const elec_comp_t)
+			syms().libelec_tie_set_list(self.comp, comps.len(),
+			    comps.as_ptr())
 		}
 	}
 	pub fn tie_set_all(&mut self, tied: bool) {
 		assert_eq!(self.get_type(), CompType::Tie);
-		unsafe { libelec_tie_set_all(self.comp, tied) }
+		unsafe { syms().libelec_tie_set_all(self.comp, tied) }
 	}
 	pub fn tie_get_all(&self) -> bool {
 		assert_eq!(self.get_type(), CompType::Tie);
-		unsafe { libelec_tie_get_all(self.comp) }
+		unsafe { syms().libelec_tie_get_all(self.comp) }
 	}
 	pub fn tie_get_list(&self) -> Vec<ElecComp> {
 		assert_eq!(self.get_type(), CompType::Tie);
-		let n_comps = unsafe { libelec_tie_get_num_buses(self.comp) };
-		let mut comps: Vec<*mut elec_comp_t> =
+		let n_comps = unsafe { syms().libelec_tie_get_num_buses(self.comp) };
+		let mut comps: Vec<*mut sys::elec_comp_t> =
 		    vec![std::ptr::null_mut(); n_comps];
 		unsafe {
-			libelec_tie_get_list(self.comp, n_comps,
-			    comps.as_mut_ptr() as *mut*mut elec_comp_t);
+			syms().libelec_tie_get_list(self.comp, n_comps,
+			    comps.as_mut_ptr());
 		};
 		comps.into_iter()
 		    .map(|c| ElecComp{comp: c})
@@ -295,177 +417,423 @@ impl ElecComp {
 	}
 	pub fn tie_get_num_buses(&self) -> usize {
 		assert_eq!(self.get_type(), CompType::Tie);
-		unsafe { libelec_tie_get_num_buses(self.comp) }
+		unsafe { syms().libelec_tie_get_num_buses(self.comp) }
 	}
 	/*
 	 * Batteries
 	 */
 	pub fn batt_get_chg_rel(&self) -> f64 {
 		assert_eq!(self.get_type(), CompType::Batt);
-		unsafe { libelec_batt_get_chg_rel(self.comp) }
+		unsafe { syms().libelec_batt_get_chg_rel(self.comp) }
 	}
 	pub fn batt_set_chg_rel(&mut self, chg_rel: f64) {
 		assert_eq!(self.get_type(), CompType::Batt);
-		unsafe { libelec_batt_set_chg_rel(self.comp, chg_rel) }
+		unsafe { syms().libelec_batt_set_chg_rel(self.comp, chg_rel) }
 	}
 	pub fn batt_get_temp(&self) -> f64 {
 		assert_eq!(self.get_type(), CompType::Batt);
-		unsafe { libelec_batt_get_temp(self.comp) }
+		unsafe { syms().libelec_batt_get_temp(self.comp) }
 	}
 	#[allow(non_snake_case)]
 	pub fn batt_set_temp(&mut self, T: f64) {
 		assert_eq!(self.get_type(), CompType::Batt);
-		unsafe { libelec_batt_set_temp(self.comp, T) }
+		unsafe { syms().libelec_batt_set_temp(self.comp, T) }
 	}
 	/*
 	 * Chargers
 	 */
 	pub fn chgr_get_working(&self) -> bool {
 		assert_eq!(self.get_type(), CompType::TRU);
-		unsafe { libelec_chgr_get_working(self.comp) }
+		unsafe { syms().libelec_chgr_get_working(self.comp) }
+	}
+	/*
+	 * Callbacks
+	 *
+	 * These are the safe counterparts of libelec_batt_set_temp_cb(),
+	 * libelec_gen_set_rpm_cb() and libelec_load_set_load_cb(): the
+	 * closure is boxed and its address is handed to libelec through
+	 * libelec_comp_set_userinfo(), which is otherwise unused by this
+	 * wrapper, so there's no risk of colliding with another use of the
+	 * component's userinfo slot. Re-registering replaces (and drops) the
+	 * previous closure; COMP_CBS is cleared for this component when its
+	 * owning ElecSys is dropped.
+	 */
+	pub fn set_temp_cb(&mut self,
+	    f: impl FnMut(&ElecComp) -> f64 + Send + 'static) {
+		assert_eq!(self.get_type(), CompType::Batt);
+		self.install_get_cb(f);
+		unsafe {
+			syms().libelec_batt_set_temp_cb(self.comp,
+			    Some(batt_temp_cb_trampoline));
+		}
+	}
+	pub fn set_rpm_cb(&mut self,
+	    f: impl FnMut(&ElecComp) -> f64 + Send + 'static) {
+		assert_eq!(self.get_type(), CompType::Gen);
+		self.install_get_cb(f);
+		unsafe {
+			syms().libelec_gen_set_rpm_cb(self.comp,
+			    Some(gen_rpm_cb_trampoline));
+		}
+	}
+	pub fn set_load_cb(&mut self,
+	    f: impl FnMut(&ElecComp) -> f64 + Send + 'static) {
+		assert_eq!(self.get_type(), CompType::Load);
+		self.install_get_cb(f);
+		unsafe {
+			syms().libelec_load_set_load_cb(self.comp,
+			    Some(load_cb_trampoline));
+		}
+	}
+	/*
+	 * Boxes `f` behind its own Arc<Mutex<_>>, repoints the component's
+	 * userinfo at it, then parks the Arc in COMP_CBS keyed by this
+	 * component's pointer, replacing (and dropping) any previously
+	 * installed closure there.
+	 *
+	 * Repointing userinfo at the new entry before dropping the old one
+	 * (below) rules out the C side ever being handed a stale pointer.
+	 * The Arc takes care of the rest: a trampoline call already in
+	 * flight against the old entry holds its own strong reference (see
+	 * comp_cb_from_userinfo()), so replacing COMP_CBS's copy here can
+	 * only drop the allocation once that call has returned.
+	 *
+	 * Each component gets its own Mutex rather than sharing one global
+	 * lock, so a callback on one component calling set_temp_cb()/
+	 * set_rpm_cb()/set_load_cb() on a *different* component -- a
+	 * reasonable thing to want to do -- can't deadlock or have its panic
+	 * poison every other component's callback. Doing so on *this* same
+	 * component from inside its own callback doesn't deadlock either:
+	 * installing only takes the COMP_CBS lock, never the per-component
+	 * one the running callback holds, and the running closure stays alive
+	 * through the trampoline's own reference until it returns.
+	 */
+	fn install_get_cb(&mut self,
+	    f: impl FnMut(&ElecComp) -> f64 + Send + 'static) {
+		let cb: Arc<Mutex<GetCb>> = Arc::new(Mutex::new(Box::new(f)));
+		let userinfo = Arc::as_ptr(&cb) as *mut c_void;
+		unsafe {
+			syms().libelec_comp_set_userinfo(self.comp, userinfo);
+		}
+		comp_cbs().lock().unwrap().insert(self.comp as usize, cb);
 	}
 }
 
+/* Shared closure type behind set_temp_cb()/set_rpm_cb()/set_load_cb(). */
+type GetCb = Box<dyn FnMut(&ElecComp) -> f64 + Send>;
+
+static COMP_CBS: OnceLock<Mutex<HashMap<usize, Arc<Mutex<GetCb>>>>> =
+    OnceLock::new();
+
+fn comp_cbs() -> &'static Mutex<HashMap<usize, Arc<Mutex<GetCb>>>> {
+	COMP_CBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
 /*
- * libelec C interface
+ * Reconstructs a strong Arc<Mutex<GetCb>> reference from the raw pointer
+ * handed to libelec as userinfo, without consuming the "real" reference
+ * that COMP_CBS owns. This keeps the callback's backing allocation alive
+ * for at least the duration of this call, even if install_get_cb() swaps
+ * (and drops) COMP_CBS's own reference out from under it on another
+ * thread while the call is in flight.
  */
-type elec_user_cb_t = extern "C" fn(*mut c_void);
-type elec_comp_walk_cb_t = extern "C" fn(*mut elec_comp_t, *mut c_void);
-type elec_get_temp_cb_t = extern "C" fn(*mut elec_comp_t,
-    userinfo: *mut c_void) -> f64;
-type elec_get_rpm_cb_t = extern "C" fn(*mut elec_comp_t,
-    userinfo: *mut c_void) -> f64;
-type elec_get_load_cb_t = extern "C" fn(*mut elec_comp_t,
-    userinfo: *mut c_void) -> f64;
-
-#[repr(C)]
-pub struct elec_t {
-	_unused: [u8; 0],
+unsafe fn comp_cb_from_userinfo(userinfo: *mut c_void) -> Arc<Mutex<GetCb>> {
+	let borrowed = Arc::from_raw(userinfo as *const Mutex<GetCb>);
+	let owned = Arc::clone(&borrowed);
+	std::mem::forget(borrowed);
+	owned
 }
 
-#[repr(C)]
-pub struct elec_comp_t {
-	_unused: [u8; 0],
+extern "C" fn batt_temp_cb_trampoline(comp: *mut sys::elec_comp_t,
+    userinfo: *mut c_void) -> f64 {
+	let cb = unsafe { comp_cb_from_userinfo(userinfo) };
+	let mut cb = cb.lock().unwrap();
+	let comp = ElecComp{comp: comp};
+	(*cb)(&comp)
+}
+extern "C" fn gen_rpm_cb_trampoline(comp: *mut sys::elec_comp_t,
+    userinfo: *mut c_void) -> f64 {
+	let cb = unsafe { comp_cb_from_userinfo(userinfo) };
+	let mut cb = cb.lock().unwrap();
+	let comp = ElecComp{comp: comp};
+	(*cb)(&comp)
+}
+extern "C" fn load_cb_trampoline(comp: *mut sys::elec_comp_t,
+    userinfo: *mut c_void) -> f64 {
+	let cb = unsafe { comp_cb_from_userinfo(userinfo) };
+	let mut cb = cb.lock().unwrap();
+	let comp = ElecComp{comp: comp};
+	(*cb)(&comp)
 }
 
-const ELEC_MAX_SRCS: usize =	64;
+/*
+ * Context behind add_pre_step()/add_post_step(): unlike the per-component
+ * callbacks above, libelec_add_user_cb() hands the trampoline nothing but
+ * the userinfo pointer, so the elec_t has to be carried alongside the
+ * closure to be able to reconstruct an &mut ElecSys for it.
+ */
+struct StepCtx {
+	elec: *mut sys::elec_t,
+	cb: Box<dyn FnMut(&mut ElecSys) + Send>,
+}
+/* Safe: `elec` is never dereferenced outside of libelec's own calls. */
+unsafe impl Send for StepCtx {}
 
-extern "C" {
-	fn libelec_new(filename: *const c_char) -> *mut elec_t;
-	fn libelec_destroy(elec: *mut elec_t);
+static STEP_CBS: OnceLock<Mutex<HashMap<usize, Vec<Box<StepCtx>>>>> =
+    OnceLock::new();
 
-	fn libelec_sys_start(elec: *mut elec_t) -> bool;
-	fn libelec_sys_stop(elec: *mut elec_t);
-	fn libelec_sys_is_started(elec: *const elec_t) -> bool;
-	fn libelec_sys_can_start(elec: *const elec_t) -> bool;
+fn step_cbs() -> &'static Mutex<HashMap<usize, Vec<Box<StepCtx>>>> {
+	STEP_CBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
 
-	fn libelec_sys_set_time_factor(elec: *mut elec_t, time_factor: f64);
-	fn libelec_sys_get_time_factor(elec: *const elec_t) -> f64;
+/*
+ * Reconstructs a borrowed ElecSys over the same `elec` pointer the
+ * closure was registered against. Wrapped in ManuallyDrop so this
+ * temporary handle doesn't run ElecSys::drop() (and tear down the very
+ * system that's mid-step) once the trampoline returns.
+ */
+extern "C" fn step_cb_trampoline(userinfo: *mut c_void) {
+	let ctx = unsafe { &mut *(userinfo as *mut StepCtx) };
+	let mut sys = std::mem::ManuallyDrop::new(ElecSys{elec: ctx.elec});
+	(ctx.cb)(&mut sys);
+}
 
-	fn libelec_add_user_cb(elec: *mut elec_t, pre: bool,
-	    cb: elec_user_cb_t, userinfo: *mut c_void);
-	fn libelec_remove_user_cb(elec: *mut elec_t, pre: bool,
-	    cb: elec_user_cb_t, userinfo: *mut c_void);
+/*
+ * Raw bindings, generated from libelec.h by build.rs via bindgen. Under
+ * the "dlopen" feature this is bindgen's own dynamic-loading output: a
+ * `libelec` struct whose constructor resolves every `libelec_*` symbol
+ * out of a `libloading::Library`. Otherwise it's a plain extern "C"
+ * block, resolved at link time as before.
+ */
+mod sys {
+	#![allow(dead_code)]
+	#![allow(non_camel_case_types)]
+	#![allow(non_upper_case_globals)]
+	include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
 
-	fn libelec_comp_find(elec: *const elec_t, name: *const c_char) ->
-	    *mut elec_comp_t;
-	fn libelec_walk_comps(elec: *const elec_t, cb: elec_comp_walk_cb_t,
-	    userinfo: *mut c_void);
+#[cfg(not(feature = "dlopen"))]
+struct Syms;
+
+#[cfg(feature = "dlopen")]
+struct Syms(sys::libelec);
+
+/*
+ * Forwards each wrapper call through to libelec, either straight to the
+ * linked symbol or through the loaded function-pointer table, depending
+ * on the "dlopen" feature. This is the only place that needs to know
+ * which of the two `Syms` shapes above is in play.
+ */
+macro_rules! syms_fwd {
+	($(fn $name:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) $(-> $ret:ty)?;)*) => {
+		impl Syms {
+			$(
+			#[allow(non_snake_case)]
+			pub(crate) unsafe fn $name(&self $(, $arg: $ty)*) $(-> $ret)? {
+				#[cfg(not(feature = "dlopen"))]
+				{ sys::$name($($arg),*) }
+				#[cfg(feature = "dlopen")]
+				{ self.0.$name($($arg),*) }
+			}
+			)*
+		}
+	};
+}
+
+syms_fwd! {
+	fn libelec_new(&self, filename: *const c_char) -> *mut sys::elec_t;
+	fn libelec_destroy(&self, elec: *mut sys::elec_t);
+
+	fn libelec_sys_start(&self, elec: *mut sys::elec_t) -> bool;
+	fn libelec_sys_stop(&self, elec: *mut sys::elec_t);
+	fn libelec_sys_is_started(&self, elec: *const sys::elec_t) -> bool;
+	fn libelec_sys_can_start(&self, elec: *const sys::elec_t) -> bool;
+
+	fn libelec_sys_set_time_factor(&self, elec: *mut sys::elec_t,
+	    time_factor: f64);
+	fn libelec_sys_get_time_factor(&self, elec: *const sys::elec_t) -> f64;
+
+	fn libelec_add_user_cb(&self, elec: *mut sys::elec_t, pre: bool,
+	    cb: sys::elec_user_cb_t, userinfo: *mut c_void);
+	fn libelec_remove_user_cb(&self, elec: *mut sys::elec_t, pre: bool,
+	    cb: sys::elec_user_cb_t, userinfo: *mut c_void);
+
+	fn libelec_comp_find(&self, elec: *const sys::elec_t,
+	    name: *const c_char) -> *mut sys::elec_comp_t;
+	fn libelec_walk_comps(&self, elec: *const sys::elec_t,
+	    cb: sys::elec_comp_walk_cb_t, userinfo: *mut c_void);
 
-	fn libelec_comp_get_num_conns(comp: *const elec_comp_t) -> usize;
-	fn libelec_comp_get_conn(comp: *const elec_comp_t, i: usize) ->
-	    *mut elec_comp_t;
-
-	fn libelec_comp_is_AC(comp: *const elec_comp_t) -> bool;
-	fn libelec_comp_get_name(comp: *const elec_comp_t) -> *const c_char;
-	fn libelec_comp_get_type(comp: *const elec_comp_t) -> CompType;
-	fn libelec_comp_get_location(comp: *const elec_comp_t) -> *const c_char;
-	fn libelec_comp_get_autogen(comp: *const elec_comp_t) -> bool;
-
-	fn libelec_comp_get_in_volts(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_out_volts(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_in_amps(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_out_amps(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_in_pwr(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_out_pwr(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_in_freq(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_out_freq(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_incap_volts(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_is_powered(comp: *const elec_comp_t) -> bool;
-	fn libelec_comp_get_eff(comp: *const elec_comp_t) -> f64;
-	fn libelec_comp_get_srcs(comp: *const elec_comp_t,
-	    srcs: &mut [*mut elec_comp_t; ELEC_MAX_SRCS]) -> usize;
-
-	fn libelec_comp_set_failed(comp: *mut elec_comp_t, failed: bool);
-	fn libelec_comp_get_failed(comp: *const elec_comp_t) -> bool;
-	fn libelec_comp_set_shorted(comp: *mut elec_comp_t, shorted: bool);
-	fn libelec_comp_get_shorted(comp: *const elec_comp_t) -> bool;
-	fn libelec_gen_set_random_volts(comp: *mut elec_comp_t,
+	fn libelec_comp_get_num_conns(&self, comp: *const sys::elec_comp_t) ->
+	    usize;
+	fn libelec_comp_get_conn(&self, comp: *const sys::elec_comp_t,
+	    i: usize) -> *mut sys::elec_comp_t;
+
+	fn libelec_comp_is_AC(&self, comp: *const sys::elec_comp_t) -> bool;
+	fn libelec_comp_get_name(&self, comp: *const sys::elec_comp_t) ->
+	    *const c_char;
+	fn libelec_comp_get_type(&self, comp: *const sys::elec_comp_t) ->
+	    sys::elec_comp_type;
+	fn libelec_comp_get_location(&self, comp: *const sys::elec_comp_t) ->
+	    *const c_char;
+	fn libelec_comp_get_autogen(&self, comp: *const sys::elec_comp_t) ->
+	    bool;
+
+	fn libelec_comp_get_in_volts(&self, comp: *const sys::elec_comp_t) ->
+	    f64;
+	fn libelec_comp_get_out_volts(&self, comp: *const sys::elec_comp_t) ->
+	    f64;
+	fn libelec_comp_get_in_amps(&self, comp: *const sys::elec_comp_t) -> f64;
+	fn libelec_comp_get_out_amps(&self, comp: *const sys::elec_comp_t) ->
+	    f64;
+	fn libelec_comp_get_in_pwr(&self, comp: *const sys::elec_comp_t) -> f64;
+	fn libelec_comp_get_out_pwr(&self, comp: *const sys::elec_comp_t) -> f64;
+	fn libelec_comp_get_in_freq(&self, comp: *const sys::elec_comp_t) -> f64;
+	fn libelec_comp_get_out_freq(&self, comp: *const sys::elec_comp_t) ->
+	    f64;
+	fn libelec_comp_get_incap_volts(&self, comp: *const sys::elec_comp_t)
+	    -> f64;
+	fn libelec_comp_is_powered(&self, comp: *const sys::elec_comp_t) -> bool;
+	fn libelec_comp_get_eff(&self, comp: *const sys::elec_comp_t) -> f64;
+	fn libelec_comp_get_srcs(&self, comp: *const sys::elec_comp_t,
+	    srcs: *mut *mut sys::elec_comp_t) -> usize;
+
+	fn libelec_comp_set_failed(&self, comp: *mut sys::elec_comp_t,
+	    failed: bool);
+	fn libelec_comp_get_failed(&self, comp: *const sys::elec_comp_t) -> bool;
+	fn libelec_comp_set_shorted(&self, comp: *mut sys::elec_comp_t,
+	    shorted: bool);
+	fn libelec_comp_get_shorted(&self, comp: *const sys::elec_comp_t) ->
+	    bool;
+	fn libelec_gen_set_random_volts(&self, comp: *mut sys::elec_comp_t,
 	    stddev: f64) -> f64;
-	fn libelec_gen_set_random_freq(comp: *mut elec_comp_t,
+	fn libelec_gen_set_random_freq(&self, comp: *mut sys::elec_comp_t,
 	    stddev: f64) -> f64;
 
-	fn libelec_comp_set_userinfo(comp: *mut elec_comp_t,
+	fn libelec_comp_set_userinfo(&self, comp: *mut sys::elec_comp_t,
 	    userinfo: *mut c_void);
-	fn libelec_comp_get_userinfo(comp: *const elec_comp_t) ->
+	fn libelec_comp_get_userinfo(&self, comp: *const sys::elec_comp_t) ->
 	    *mut c_void;
 
-	fn libelec_batt_set_temp_cb(batt: *mut elec_comp_t,
-	    cb: elec_get_temp_cb_t);
-	fn libelec_batt_get_temp_cb(batt: *const elec_comp_t) ->
-	    elec_get_temp_cb_t;
-
-	fn libelec_gen_set_rpm_cb(gen: *mut elec_comp_t,
-	    cb: elec_get_rpm_cb_t);
-	fn libelec_gen_get_rpm_cb(gen: *const elec_comp_t) ->
-	    elec_get_rpm_cb_t;
-
-	fn libelec_load_set_load_cb(load: *mut elec_comp_t,
-	    cb: elec_get_load_cb_t);
-	fn libelec_load_get_load_cb(load: *const elec_comp_t) ->
-	    elec_get_load_cb_t;
-
-	fn libelec_cb_set(comp: *mut elec_comp_t, set: bool);
-	fn libelec_cb_get(comp: *const elec_comp_t) -> bool;
-	fn libelec_cb_get_temp(comp: *const elec_comp_t) -> f64;
-
-	fn libelec_tie_set_list(comp: *mut elec_comp_t, list_len: usize,
-	    bus_list: *const*const elec_comp_t);
-	fn libelec_tie_set_all(comp: *mut elec_comp_t, tied: bool);
-	fn libelec_tie_get_all(comp: *const elec_comp_t) -> bool;
-	fn libelec_tie_get_list(comp: *const elec_comp_t, cap: usize,
-	    bus_list: *mut*mut elec_comp_t) -> usize;
-	fn libelec_tie_get_num_buses(comp: *const elec_comp_t) -> usize;
-
-	fn libelec_batt_get_chg_rel(batt: *const elec_comp_t) -> f64;
-	fn libelec_batt_set_chg_rel(batt: *mut elec_comp_t, chg_rel: f64);
-	fn libelec_batt_get_temp(batt: *const elec_comp_t) -> f64;
-	fn libelec_batt_set_temp(batt: *mut elec_comp_t, T: f64);
-
-	fn libelec_chgr_get_working(chgr: *const elec_comp_t) -> bool;
-
-	pub fn libelec_phys_get_batt_voltage(U_nominal: f64, chg_rel: f64,
+	fn libelec_batt_set_temp_cb(&self, batt: *mut sys::elec_comp_t,
+	    cb: sys::elec_get_temp_cb_t);
+	fn libelec_batt_get_temp_cb(&self, batt: *const sys::elec_comp_t) ->
+	    sys::elec_get_temp_cb_t;
+
+	fn libelec_gen_set_rpm_cb(&self, gen: *mut sys::elec_comp_t,
+	    cb: sys::elec_get_rpm_cb_t);
+	fn libelec_gen_get_rpm_cb(&self, gen: *const sys::elec_comp_t) ->
+	    sys::elec_get_rpm_cb_t;
+
+	fn libelec_load_set_load_cb(&self, load: *mut sys::elec_comp_t,
+	    cb: sys::elec_get_load_cb_t);
+	fn libelec_load_get_load_cb(&self, load: *const sys::elec_comp_t) ->
+	    sys::elec_get_load_cb_t;
+
+	fn libelec_cb_set(&self, comp: *mut sys::elec_comp_t, set: bool);
+	fn libelec_cb_get(&self, comp: *const sys::elec_comp_t) -> bool;
+	fn libelec_cb_get_temp(&self, comp: *const sys::elec_comp_t) -> f64;
+
+	fn libelec_tie_set_list(&self, comp: *mut sys::elec_comp_t,
+	    list_len: usize, bus_list: *const *const sys::elec_comp_t);
+	fn libelec_tie_set_all(&self, comp: *mut sys::elec_comp_t, tied: bool);
+	fn libelec_tie_get_all(&self, comp: *const sys::elec_comp_t) -> bool;
+	fn libelec_tie_get_list(&self, comp: *const sys::elec_comp_t,
+	    cap: usize, bus_list: *mut *mut sys::elec_comp_t) -> usize;
+	fn libelec_tie_get_num_buses(&self, comp: *const sys::elec_comp_t) ->
+	    usize;
+
+	fn libelec_batt_get_chg_rel(&self, batt: *const sys::elec_comp_t) ->
+	    f64;
+	fn libelec_batt_set_chg_rel(&self, batt: *mut sys::elec_comp_t,
+	    chg_rel: f64);
+	fn libelec_batt_get_temp(&self, batt: *const sys::elec_comp_t) -> f64;
+	fn libelec_batt_set_temp(&self, batt: *mut sys::elec_comp_t, T: f64);
+
+	fn libelec_chgr_get_working(&self, chgr: *const sys::elec_comp_t) ->
+	    bool;
+
+	fn libelec_phys_get_batt_voltage(&self, U_nominal: f64, chg_rel: f64,
 	    I_rel: f64) -> f64;
 }
 
+#[cfg(feature = "dlopen")]
+#[cfg(target_os = "linux")]
+const DEFAULT_LIB_NAME: &str = "libelec.so";
+#[cfg(feature = "dlopen")]
+#[cfg(target_os = "macos")]
+const DEFAULT_LIB_NAME: &str = "libelec.dylib";
+#[cfg(feature = "dlopen")]
+#[cfg(target_os = "windows")]
+const DEFAULT_LIB_NAME: &str = "elec.dll";
+
+#[cfg(feature = "dlopen")]
+#[derive(Debug)]
+pub enum LibElecError {
+	/// The library itself (or one of its expected symbols) couldn't be
+	/// loaded.
+	Load(libloading::Error),
+	/// The library loaded, but `libelec_new()` failed to parse the net.
+	Init,
+}
+
+#[cfg(feature = "dlopen")]
+impl std::fmt::Display for LibElecError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			LibElecError::Load(e) =>
+			    write!(f, "error loading libelec: {}", e),
+			LibElecError::Init =>
+			    write!(f, "libelec_new() failed"),
+		}
+	}
+}
+
+#[cfg(feature = "dlopen")]
+impl std::error::Error for LibElecError {}
+
+static SYMS: OnceLock<Syms> = OnceLock::new();
+
+/*
+ * Every wrapper method above other than ElecSys::new() reaches libelec
+ * exclusively through this accessor. Panics if called before
+ * ElecSys::new() has first set up SYMS, since there is no meaningful
+ * fallback at that point.
+ */
+fn syms() -> &'static Syms {
+	SYMS.get().expect("libelec is not loaded; call ElecSys::new() first")
+}
+
 mod tests {
 	const TEST_NET_FILE: &str = "../nettest/test.net";
 	extern "C" {
 		fn crc64_init();
 	}
+	/*
+	 * ElecSys::new()'s signature differs under the "dlopen" feature (it
+	 * takes an extra library-path argument and returns a Result instead
+	 * of an Option), so the tests below go through this helper rather
+	 * than calling it directly, to stay buildable under both feature
+	 * configurations.
+	 */
+	#[cfg(not(feature = "dlopen"))]
+	fn load_test_net() -> crate::ElecSys {
+		crate::ElecSys::new(TEST_NET_FILE)
+		    .expect(&format!("Failed to load net {}", TEST_NET_FILE))
+	}
+	#[cfg(feature = "dlopen")]
+	fn load_test_net() -> crate::ElecSys {
+		crate::ElecSys::new(TEST_NET_FILE, None)
+		    .expect(&format!("Failed to load net {}", TEST_NET_FILE))
+	}
 	#[test]
 	fn load_net() {
-		use crate::ElecSys;
 		unsafe { crc64_init() };
-		ElecSys::new(TEST_NET_FILE)
-		    .expect(&format!("Failed to load net {}", TEST_NET_FILE));
+		load_test_net();
 	}
 	#[test]
 	fn load_and_run_net() {
-		use crate::ElecSys;
 		unsafe { crc64_init() };
-		let mut sys = ElecSys::new(TEST_NET_FILE)
-		    .expect(&format!("Failed to load net {}", TEST_NET_FILE));
+		let mut sys = load_test_net();
 		sys.start();
 		std::thread::sleep(std::time::Duration::new(1, 0));
 		for comp in sys.all_comps().iter() {
@@ -482,11 +850,31 @@ mod tests {
 		}
 	}
 	#[test]
+	fn batt_temp_cb_invoked() {
+		use crate::CompType;
+		use std::sync::atomic::AtomicBool;
+		use std::sync::atomic::Ordering;
+		use std::sync::Arc;
+		unsafe { crc64_init() };
+		let mut sys = load_test_net();
+		let mut batt = sys.all_comps().into_iter()
+		    .find(|c| !c.get_autogen() && c.get_type() == CompType::Batt)
+		    .expect("test net has no battery");
+		let invoked = Arc::new(AtomicBool::new(false));
+		let invoked_cb = Arc::clone(&invoked);
+		batt.set_temp_cb(move |comp| {
+			assert_eq!(comp.get_type(), CompType::Batt);
+			invoked_cb.store(true, Ordering::SeqCst);
+			15.0
+		});
+		sys.start();
+		std::thread::sleep(std::time::Duration::new(1, 0));
+		assert!(invoked.load(Ordering::SeqCst));
+	}
+	#[test]
 	fn list_all_comps() {
-		use crate::ElecSys;
 		unsafe { crc64_init() };
-		let sys = ElecSys::new(TEST_NET_FILE)
-		    .expect(&format!("Failed to load net {}", TEST_NET_FILE));
+		let sys = load_test_net();
 		for comp in sys.all_comps().iter() {
 			if !comp.get_autogen() {
 				println!(concat!(